@@ -6,9 +6,29 @@
 //! [serde_json::from_iter](https://docs.serde.rs/serde_json/de/fn.from_iter.html).
 //!
 //! This implementation supports chunk lengths up to that which can be stored by `usize`
-//! on the target platform. Chunk extension parameters are discarded, and trailing headers
-//! aren't processed, although they can be retrieved from the wrapped source iterator at
-//! the end of chunked payload iteration.
+//! on the target platform. Chunk extension parameters and trailing headers are ignored
+//! by default, but both can optionally be parsed out, as described below.
+//!
+//! For the producing side of a connection, [`encode`](fn.encode.html) and
+//! [`ChunkedEncoder`](struct.ChunkedEncoder.html) frame payload bytes into the same
+//! chunked-encoding format.
+//!
+//! When the whole stream is already available in a single buffer,
+//! [`ChunkedSlices`](struct.ChunkedSlices.html) yields borrowed `&[u8]` chunks
+//! instead of individual bytes, avoiding per-byte iteration overhead. It accepts the
+//! same `strict` and `max_ext_len` options as `ChunkedBytes`, so switching to it for
+//! throughput doesn't give up those protections.
+//!
+//! Chunk extensions can optionally be captured with
+//! [`capture_extensions`](struct.ChunkedBytes.html#method.capture_extensions) and
+//! [`extension`](struct.ChunkedBytes.html#method.extension), and trailing header
+//! fields can be parsed directly with
+//! [`trailers`](struct.ChunkedBytes.html#method.trailers) instead of being recovered
+//! by hand from the wrapped source iterator.
+//!
+//! [`BodyDecoder`](enum.BodyDecoder.html) generalizes over all three ways an HTTP/1
+//! body can be delimited (chunked, `Content-Length`, or connection-close), for
+//! callers that need to pick a decoding mode from parsed headers.
 //!
 //! ## Example
 //!
@@ -34,6 +54,101 @@
 //! assert!(bytes.next().is_none());
 //! ```
 
+/// Encode `data` as a single complete chunked-encoding message: the chunk framing for
+/// `data` followed immediately by the terminating `0\r\n\r\n` [RFC7230§4.1].
+///
+/// This is a one-shot convenience for the common case of a body that fits in a single
+/// chunk. For a body built up from several chunks, use `ChunkedEncoder` instead.
+///
+/// A zero-length `data` isn't framed as its own chunk (a zero-length chunk is the
+/// terminator, not a real chunk), so `encode(b"")` is just the bare terminator.
+///
+/// ## Example
+///
+/// ```rust
+/// use uhttp_chunked_bytes::encode;
+///
+/// assert_eq!(encode(b"hello"), b"5\r\nhello\r\n0\r\n\r\n");
+/// assert_eq!(encode(b""), b"0\r\n\r\n");
+/// ```
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(data.len() + 11);
+
+    if !data.is_empty() {
+        encode_chunk(data, &mut buf);
+    }
+
+    buf.extend_from_slice(b"0\r\n\r\n");
+    buf
+}
+
+/// Append the chunked-encoding framing for a single chunk of `data` to `buf`: the
+/// chunk size in ASCII hex, `\r\n`, the chunk data, then `\r\n`.
+fn encode_chunk(data: &[u8], buf: &mut Vec<u8>) {
+    buf.extend_from_slice(format!("{:x}", data.len()).as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Streaming encoder that frames successive payload chunks in HTTP
+/// [chunked-encoding](https://tools.ietf.org/html/rfc7230#section-4.1) format.
+///
+/// Push each chunk of payload data with [`push`](#method.push), then call
+/// [`finish`](#method.finish) to append the terminating chunk and get back the
+/// complete encoded byte sequence.
+///
+/// ## Example
+///
+/// ```rust
+/// use uhttp_chunked_bytes::ChunkedEncoder;
+///
+/// let mut enc = ChunkedEncoder::new();
+/// enc.push(b"hello ");
+/// enc.push(b"world");
+///
+/// assert_eq!(enc.finish(&[]), b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n");
+/// ```
+#[derive(Debug, Default)]
+pub struct ChunkedEncoder {
+    /// Encoded bytes accumulated so far.
+    buf: Vec<u8>,
+}
+
+impl ChunkedEncoder {
+    /// Create a new, empty `ChunkedEncoder`.
+    pub fn new() -> Self {
+        ChunkedEncoder { buf: Vec::new() }
+    }
+
+    /// Encode `data` as the next chunk and append its framing to the output.
+    ///
+    /// A zero-length `data` is a no-op: a zero-length chunk is the terminator, not a
+    /// real chunk, so pushing one here would prematurely end the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        if !data.is_empty() {
+            encode_chunk(data, &mut self.buf);
+        }
+    }
+
+    /// Finish the chunked stream and return the accumulated output.
+    ///
+    /// `trailers` is a list of trailing header-field lines (each already in `Name:
+    /// Value` form, without a trailing CRLF) inserted before the final CRLF that ends
+    /// the message [RFC7230§4.1.2].
+    pub fn finish(mut self, trailers: &[&str]) -> Vec<u8> {
+        self.buf.extend_from_slice(b"0\r\n");
+
+        for trailer in trailers {
+            self.buf.extend_from_slice(trailer.as_bytes());
+            self.buf.extend_from_slice(b"\r\n");
+        }
+
+        self.buf.extend_from_slice(b"\r\n");
+        self.buf
+    }
+}
+
 /// A 64-bit usize number can have at most 16 hex digits.
 #[cfg(target_pointer_width = "64")]
 type DigitBuf = [u8; 16];
@@ -42,16 +157,66 @@ type DigitBuf = [u8; 16];
 #[cfg(target_pointer_width = "32")]
 type DigitBuf = [u8; 8];
 
+/// A stage of parsing a chunked-encoded stream, persisted on `ChunkedBytes` so that a
+/// `WouldBlock` from the wrapped source can't corrupt or lose partial progress.
+///
+/// Modeled on the `ChunkedState` machines used by actix and hyper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkedState {
+    /// Reading the hex digits of a chunk-size line.
+    Size,
+    /// Reading the bytes of a chunk extension, up to its terminating CRLF (discarded
+    /// unless `capture_extensions` is enabled).
+    SizeExt,
+    /// Expecting the LF that terminates a chunk-size line.
+    SizeLf,
+    /// Yielding payload bytes of the current chunk.
+    Body,
+    /// Expecting the CR that terminates a chunk's payload.
+    BodyCr,
+    /// Expecting the LF that terminates a chunk's payload.
+    BodyLf,
+    /// A zero-length chunk has been seen; the chunked stream is finished.
+    End,
+}
+
 /// Iterator over payload bytes in a chunked-encoded stream.
 ///
 /// When the iterator returns `None`, the wrapped stream will typically contain a final
 /// CRLF to end the body, but it may also contain [trailing header
 /// fields](https://tools.ietf.org/html/rfc7230#section-4.1.2) before the final CRLF.
+///
+/// Parsing state is held entirely in the struct rather than in local variables, so if
+/// the wrapped iterator yields an `io::Error` with `ErrorKind::WouldBlock` partway
+/// through a chunk-size line or trailing CRLF, no progress is lost: a later call to
+/// `next()` resumes parsing exactly where it left off.
 pub struct ChunkedBytes<I: Iterator<Item = std::io::Result<u8>>> {
     /// Underlying byte stream in chunked transfer-encoding format.
     stream: I,
-    /// Number of remaining bytes in the current chunk.
+    /// Current stage of parsing.
+    state: ChunkedState,
+    /// Number of remaining bytes in the current chunk, valid once `state` has moved
+    /// past `SizeLf`.
     remain: usize,
+    /// Hex digits of the chunk size accumulated so far in the `Size` state.
+    digits: DigitBuf,
+    /// Number of hex digits accumulated in `digits`.
+    digits_len: usize,
+    /// Number of chunk-extension bytes seen so far in the `SizeExt` state.
+    ext_len: usize,
+    /// Chunk-extension bytes of the most recently parsed chunk-size line, captured
+    /// only when `capture_extensions` is enabled.
+    ext: Vec<u8>,
+    /// Last payload byte read in the `Body` state, held until the chunk's trailing
+    /// CRLF has been verified.
+    last_byte: u8,
+    /// Whether to reject any byte in a chunk-size line that isn't a hex digit,
+    /// `;`, or CRLF, instead of deferring validation to `from_str_radix`.
+    strict: bool,
+    /// Maximum number of bytes allowed in a chunk extension, or `None` for no limit.
+    max_ext_len: Option<usize>,
+    /// Whether to record chunk-extension bytes into `ext` for later retrieval.
+    capture_extensions: bool,
 }
 
 impl<I: Iterator<Item = std::io::Result<u8>>> ChunkedBytes<I> {
@@ -59,137 +224,563 @@ impl<I: Iterator<Item = std::io::Result<u8>>> ChunkedBytes<I> {
     pub fn new(stream: I) -> Self {
         ChunkedBytes {
             stream: stream,
+            state: ChunkedState::Size,
             remain: 0,
+            digits: DigitBuf::default(),
+            digits_len: 0,
+            ext_len: 0,
+            ext: Vec::new(),
+            last_byte: 0,
+            strict: false,
+            max_ext_len: None,
+            capture_extensions: false,
         }
     }
 
-    /// Parse the number of bytes in the next chunk.
-    fn parse_size(&mut self) -> Option<std::io::Result<usize>> {
-        let mut digits = DigitBuf::default();
+    /// Enable strict validation of the chunk-size line, rejecting any byte that isn't
+    /// a valid hex digit before the `;` or CRLF (for example embedded whitespace like
+    /// `"0 \r\n"`), rather than deferring to `from_str_radix`. Disabled by default to
+    /// match the existing lenient behavior.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-        let slice = match self.parse_digits(&mut digits[..]) {
-            // This is safe because the following call to `from_str_radix` does
-            // its own verification on the bytes.
-            Some(Ok(s)) => unsafe { std::str::from_utf8_unchecked(s) },
-            Some(Err(e)) => return Some(Err(e)),
-            None => return None,
+    /// Set the maximum number of bytes allowed in a chunk extension before the
+    /// size line is rejected with `InvalidData`. `None` (the default) leaves the
+    /// extension length unbounded.
+    pub fn max_ext_len(mut self, max_ext_len: Option<usize>) -> Self {
+        self.max_ext_len = max_ext_len;
+        self
+    }
+
+    /// Enable capturing chunk-extension bytes so they can be retrieved afterward
+    /// with [`extension`](#method.extension), instead of only being discarded.
+    /// Disabled by default to preserve the zero-allocation default behavior.
+    pub fn capture_extensions(mut self, capture: bool) -> Self {
+        self.capture_extensions = capture;
+        self
+    }
+
+    /// The chunk-extension bytes of the most recently parsed chunk-size line (the raw
+    /// text following the `;`, up to but not including the terminating CRLF), or an
+    /// empty slice if none were present or `capture_extensions` wasn't enabled.
+    pub fn extension(&self) -> &[u8] {
+        &self.ext
+    }
+
+    /// Parse the hex digits accumulated in `digits` into a chunk size.
+    fn finish_size(&mut self) -> std::io::Result<usize> {
+        // Outside `strict` mode, `digits` may hold arbitrary non-hex-digit bytes
+        // (including non-UTF8 ones), so this can't assume valid UTF-8.
+        let slice = match std::str::from_utf8(&self.digits[..self.digits_len]) {
+            Ok(s) => s,
+            Err(_) => return Err(std::io::ErrorKind::InvalidData.into()),
         };
 
         match usize::from_str_radix(slice, 16) {
-            Ok(n) => Some(Ok(n)),
-            Err(_) => Some(Err(std::io::ErrorKind::InvalidData.into())),
+            Ok(n) => Ok(n),
+            Err(_) => Err(std::io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    /// Parse the trailing header-field section that follows the final chunk
+    /// [RFC7230§4.1.2], once iteration has finished (`next()` has returned `None`),
+    /// and return the parsed `(name, value)` pairs.
+    ///
+    /// This consumes the rest of the wrapped stream up through the terminating CRLF.
+    /// It's an error to call this before the chunked payload has been fully iterated.
+    pub fn trailers(&mut self) -> std::io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        if self.state != ChunkedState::End {
+            return Err(std::io::ErrorKind::Other.into());
+        }
+
+        let mut trailers = Vec::new();
+
+        loop {
+            let mut name = Vec::new();
+
+            match self.stream.next() {
+                Some(Ok(b'\r')) => return match self.stream.next() {
+                    Some(Ok(b'\n')) => Ok(trailers),
+                    Some(Ok(_)) => Err(std::io::ErrorKind::InvalidData.into()),
+                    Some(Err(e)) => Err(e),
+                    None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+                },
+                Some(Ok(b)) => name.push(b),
+                Some(Err(e)) => return Err(e),
+                None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            }
+
+            loop {
+                match self.stream.next() {
+                    Some(Ok(b':')) => break,
+                    Some(Ok(b)) => name.push(b),
+                    Some(Err(e)) => return Err(e),
+                    None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+
+            let mut value = Vec::new();
+
+            // Skip a single leading space, per RFC7230's `OWS` allowance.
+            match self.stream.next() {
+                Some(Ok(b' ')) => {},
+                Some(Ok(b)) => value.push(b),
+                Some(Err(e)) => return Err(e),
+                None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            }
+
+            loop {
+                match self.stream.next() {
+                    Some(Ok(b'\r')) => match self.stream.next() {
+                        Some(Ok(b'\n')) => break,
+                        Some(Ok(_)) => return Err(std::io::ErrorKind::InvalidData.into()),
+                        Some(Err(e)) => return Err(e),
+                        None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                    },
+                    Some(Ok(b)) => value.push(b),
+                    Some(Err(e)) => return Err(e),
+                    None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+
+            trailers.push((name, value));
         }
     }
+}
 
-    /// Extract the hex digits for the current chunk size.
-    fn parse_digits<'a>(&mut self, digits: &'a mut [u8])
-        -> Option<std::io::Result<&'a [u8]>>
-    {
-        // Number of hex digits that have been extracted.
-        let mut len = 0;
+impl<I: Iterator<Item = std::io::Result<u8>>> Iterator for ChunkedBytes<I> {
+    type Item = std::io::Result<u8>;
 
+    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let b = match self.stream.next() {
-                Some(Ok(b)) => b,
-                Some(Err(e)) => return Some(Err(e)),
-                None => return if len == 0 {
-                    // If EOF at the beginning of a new chunk, the stream is finished.
-                    None
-                } else {
-                    Some(Err(std::io::ErrorKind::UnexpectedEof.into()))
+            match self.state {
+                ChunkedState::End => return None,
+
+                ChunkedState::Size => {
+                    let b = match self.stream.next() {
+                        Some(Ok(b)) => b,
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return if self.digits_len == 0 {
+                            // If EOF at the beginning of a new chunk, the stream is
+                            // finished.
+                            None
+                        } else {
+                            Some(Err(std::io::ErrorKind::UnexpectedEof.into()))
+                        },
+                    };
+
+                    match b {
+                        b'\r' => {
+                            if self.capture_extensions {
+                                self.ext.clear();
+                            }
+
+                            self.state = ChunkedState::SizeLf;
+                        },
+                        b';' => {
+                            if self.capture_extensions {
+                                self.ext.clear();
+                            }
+
+                            self.state = ChunkedState::SizeExt;
+                        },
+                        // A bare LF not preceded by a CR is never valid here.
+                        b'\n' => return Some(Err(std::io::ErrorKind::InvalidData.into())),
+                        _ => {
+                            if self.strict && !b.is_ascii_hexdigit() {
+                                return Some(Err(std::io::ErrorKind::InvalidData.into()));
+                            }
+
+                            match self.digits.get_mut(self.digits_len) {
+                                Some(d) => *d = b,
+                                None => return Some(Err(std::io::ErrorKind::Other.into())),
+                            }
+
+                            self.digits_len += 1;
+                        },
+                    }
                 },
-            };
 
-            match b {
-                b'\r' => if let Err(e) = self.consume_lf() {
-                    return Some(Err(e));
-                } else {
-                    break;
+                ChunkedState::SizeExt => {
+                    match self.stream.next() {
+                        Some(Ok(b'\r')) => self.state = ChunkedState::SizeLf,
+                        // A bare LF not preceded by a CR is never valid here.
+                        Some(Ok(b'\n')) => return Some(Err(std::io::ErrorKind::InvalidData.into())),
+                        Some(Ok(b)) => {
+                            self.ext_len += 1;
+
+                            if let Some(max) = self.max_ext_len {
+                                if self.ext_len > max {
+                                    return Some(Err(std::io::ErrorKind::InvalidData.into()));
+                                }
+                            }
+
+                            if self.capture_extensions {
+                                self.ext.push(b);
+                            }
+                        },
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
+                    }
                 },
-                b';' => if let Err(e) = self.consume_ext() {
-                    return Some(Err(e));
-                } else {
-                    break;
+
+                ChunkedState::SizeLf => {
+                    match self.stream.next() {
+                        Some(Ok(b'\n')) => {
+                            let size = match self.finish_size() {
+                                Ok(n) => n,
+                                Err(e) => return Some(Err(e)),
+                            };
+
+                            self.digits_len = 0;
+                            self.ext_len = 0;
+
+                            // If chunk size is zero (final chunk), the stream is
+                            // finished [RFC7230§4.1].
+                            if size == 0 {
+                                self.state = ChunkedState::End;
+                            } else {
+                                self.remain = size;
+                                self.state = ChunkedState::Body;
+                            }
+                        },
+                        Some(Ok(_)) => return Some(Err(std::io::ErrorKind::InvalidData.into())),
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
+                    }
+                },
+
+                ChunkedState::Body => {
+                    let b = match self.stream.next() {
+                        Some(Ok(b)) => b,
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
+                    };
+
+                    self.remain -= 1;
+
+                    if self.remain == 0 {
+                        // Don't yield the chunk's last byte until its trailing CRLF
+                        // has been verified [RFC7230§4.1].
+                        self.last_byte = b;
+                        self.state = ChunkedState::BodyCr;
+                    } else {
+                        return Some(Ok(b));
+                    }
                 },
-                _ => {
-                    match digits.get_mut(len) {
-                        Some(d) => *d = b,
-                        None => return Some(Err(std::io::ErrorKind::Other.into())),
+
+                ChunkedState::BodyCr => {
+                    match self.stream.next() {
+                        Some(Ok(b'\r')) => self.state = ChunkedState::BodyLf,
+                        Some(Ok(_)) => return Some(Err(std::io::ErrorKind::InvalidData.into())),
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
                     }
+                },
 
-                    len += 1;
+                ChunkedState::BodyLf => {
+                    match self.stream.next() {
+                        Some(Ok(b'\n')) => {
+                            self.state = ChunkedState::Size;
+                            return Some(Ok(self.last_byte));
+                        },
+                        Some(Ok(_)) => return Some(Err(std::io::ErrorKind::InvalidData.into())),
+                        Some(Err(e)) => return Some(Err(e)),
+                        None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
+                    }
                 },
             }
         }
-
-        Some(Ok(&digits[..len]))
     }
+}
 
-    /// Consume and discard current chunk extension.
-    ///
-    /// This doesn't check whether the characters up to CRLF actually have correct syntax.
-    fn consume_ext(&mut self) -> std::io::Result<()> {
-        loop {
-            match self.stream.next() {
-                Some(Ok(b'\r')) => return self.consume_lf(),
-                Some(Ok(_)) => {},
-                Some(Err(e)) => return Err(e),
-                None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
-            }
+/// Iterator over contiguous slices of chunk-body bytes in an already-buffered
+/// chunked-encoded stream.
+///
+/// Unlike `ChunkedBytes`, which yields one byte per `next()` call from a possibly
+/// unbuffered source, `ChunkedSlices` operates directly on a byte slice already held
+/// in memory and hands back windows into it, copying nothing. This trades
+/// `ChunkedBytes`'s ability to drive an arbitrary per-byte iterator for close to
+/// memcpy speed on large, fully-buffered bodies. It supports the same
+/// [`strict`](#method.strict) and [`max_ext_len`](#method.max_ext_len) validation as
+/// `ChunkedBytes`, so it doesn't trade away those protections for throughput.
+///
+/// ## Example
+///
+/// ```rust
+/// use uhttp_chunked_bytes::ChunkedSlices;
+///
+/// let body = b"4\r\nabcd\r\n3\r\nefg\r\n0\r\n\r\n";
+/// let mut slices = ChunkedSlices::new(body);
+///
+/// assert_eq!(slices.next().unwrap().unwrap(), b"abcd");
+/// assert_eq!(slices.next().unwrap().unwrap(), b"efg");
+/// assert!(slices.next().is_none());
+/// ```
+pub struct ChunkedSlices<'a> {
+    /// Buffer holding the chunked-encoding stream.
+    buf: &'a [u8],
+    /// Offset of the next unread byte in `buf`.
+    pos: usize,
+    /// Number of remaining bytes in the current chunk.
+    remain: usize,
+    /// Whether to reject any byte in a chunk-size line that isn't a hex digit,
+    /// `;`, or CRLF, instead of deferring validation to `from_str_radix`.
+    strict: bool,
+    /// Maximum number of bytes allowed in a chunk extension, or `None` for no limit.
+    max_ext_len: Option<usize>,
+}
+
+impl<'a> ChunkedSlices<'a> {
+    /// Create a new `ChunkedSlices` iterator over the given buffer.
+    pub fn new(buf: &'a [u8]) -> Self {
+        ChunkedSlices {
+            buf: buf,
+            pos: 0,
+            remain: 0,
+            strict: false,
+            max_ext_len: None,
         }
     }
 
-    /// Verify the next bytes in the stream are CRLF.
-    fn consume_crlf(&mut self) -> std::io::Result<()> {
-        match self.stream.next() {
-            Some(Ok(b'\r')) => self.consume_lf(),
-            Some(Ok(_)) => Err(std::io::ErrorKind::InvalidData.into()),
-            Some(Err(e)) => Err(e),
-            None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+    /// Enable strict validation of the chunk-size line, rejecting any byte that isn't
+    /// a valid hex digit before the `;` or CRLF, rather than deferring to
+    /// `from_str_radix`. Disabled by default, matching `ChunkedBytes::strict`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the maximum number of bytes allowed in a chunk extension before the
+    /// size line is rejected with `InvalidData`. `None` (the default) leaves the
+    /// extension length unbounded, matching `ChunkedBytes::max_ext_len`.
+    pub fn max_ext_len(mut self, max_ext_len: Option<usize>) -> Self {
+        self.max_ext_len = max_ext_len;
+        self
+    }
+
+    /// Parse the chunk-size line starting at `pos`, advancing past it, and return the
+    /// parsed chunk size, or `None` if `pos` is already at the end of the buffer.
+    fn parse_size_line(&mut self) -> std::io::Result<Option<usize>> {
+        if self.pos == self.buf.len() {
+            return Ok(None);
+        }
+
+        let rest = &self.buf[self.pos..];
+
+        let line_end = match find_crlf(rest) {
+            Some(n) => n + 2,
+            None => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+        };
+
+        // Chunk extensions (after `;`) are discarded; only the leading hex digits
+        // matter for the size.
+        let digits_end = rest[..line_end].iter()
+            .position(|&b| b == b'\r' || b == b';')
+            .unwrap_or(line_end);
+
+        if self.strict && !rest[..digits_end].iter().all(u8::is_ascii_hexdigit) {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+
+        if rest[digits_end] == b';' {
+            let ext_len = line_end - 2 - (digits_end + 1);
+
+            if let Some(max) = self.max_ext_len {
+                if ext_len > max {
+                    return Err(std::io::ErrorKind::InvalidData.into());
+                }
+            }
         }
+
+        let slice = match std::str::from_utf8(&rest[..digits_end]) {
+            Ok(s) => s,
+            Err(_) => return Err(std::io::ErrorKind::InvalidData.into()),
+        };
+
+        let size = match usize::from_str_radix(slice, 16) {
+            Ok(n) => n,
+            Err(_) => return Err(std::io::ErrorKind::InvalidData.into()),
+        };
+
+        self.pos += line_end;
+        Ok(Some(size))
     }
 
-    /// Verify the next byte in the stream is LF.
-    fn consume_lf(&mut self) -> std::io::Result<()> {
-        match self.stream.next() {
-            Some(Ok(b'\n')) => Ok(()),
-            Some(Ok(_)) => Err(std::io::ErrorKind::InvalidData.into()),
-            Some(Err(e)) => Err(e),
+    /// Verify the bytes at `pos` are a CRLF and advance past them.
+    fn consume_crlf(&mut self) -> std::io::Result<()> {
+        match self.buf.get(self.pos..self.pos + 2) {
+            Some(b"\r\n") => {
+                self.pos += 2;
+                Ok(())
+            },
+            Some(_) => Err(std::io::ErrorKind::InvalidData.into()),
             None => Err(std::io::ErrorKind::UnexpectedEof.into()),
         }
     }
 }
 
-impl<I: Iterator<Item = std::io::Result<u8>>> Iterator for ChunkedBytes<I> {
-    type Item = std::io::Result<u8>;
+impl<'a> Iterator for ChunkedSlices<'a> {
+    type Item = std::io::Result<&'a [u8]>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.remain == 0 {
-            let size = match self.parse_size() {
-                Some(Ok(s)) => s,
-                Some(Err(e)) => return Some(Err(e)),
-                None => return None,
-            };
-
-            // If chunk size is zero (final chunk), the stream is finished [RFC7230§4.1].
-            if size == 0 {
-                return None;
+            match self.parse_size_line() {
+                Ok(Some(0)) => return None,
+                Ok(Some(size)) => self.remain = size,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
             }
+        }
 
-            self.remain = size;
+        if self.pos == self.buf.len() {
+            return Some(Err(std::io::ErrorKind::UnexpectedEof.into()));
         }
 
-        let next = self.stream.next();
-        self.remain -= 1;
+        let take = std::cmp::min(self.remain, self.buf.len() - self.pos);
+        let slice = &self.buf[self.pos..self.pos + take];
+
+        self.pos += take;
+        self.remain -= take;
 
-        // If current chunk is finished, verify it ends with CRLF [RFC7230§4.1].
         if self.remain == 0 {
             if let Err(e) = self.consume_crlf() {
                 return Some(Err(e));
             }
         }
 
-        next
+        Some(Ok(slice))
+    }
+}
+
+/// Find the first `\r` in `buf`, scanning with a raw pointer cursor in the manner of
+/// httparse, and return its index if it's immediately followed by `\n`. Returns `None`
+/// if there's no `\r`, or if the first `\r` isn't followed by `\n` (a later `\r\n` is
+/// not searched for).
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    unsafe {
+        let start = buf.as_ptr();
+        let end = start.add(buf.len());
+        let mut cur = start;
+
+        while cur < end {
+            if *cur == b'\r' {
+                let next = cur.add(1);
+
+                if next < end && *next == b'\n' {
+                    return Some(cur.offset_from(start) as usize);
+                }
+
+                return None;
+            }
+
+            cur = cur.add(1);
+        }
+
+        None
+    }
+}
+
+/// Iterator over a fixed-length, `Content-Length`-delimited body [RFC7230§3.3.3].
+///
+/// Yields exactly the given number of bytes from the wrapped stream, then stops,
+/// erroring with `UnexpectedEof` if the stream ends early.
+pub struct LengthBytes<I: Iterator<Item = std::io::Result<u8>>> {
+    /// Underlying byte stream.
+    stream: I,
+    /// Number of remaining bytes in the body.
+    remain: u64,
+}
+
+impl<I: Iterator<Item = std::io::Result<u8>>> LengthBytes<I> {
+    /// Create a new `LengthBytes` iterator yielding `len` bytes from `stream`.
+    pub fn new(stream: I, len: u64) -> Self {
+        LengthBytes {
+            stream: stream,
+            remain: len,
+        }
+    }
+}
+
+impl<I: Iterator<Item = std::io::Result<u8>>> Iterator for LengthBytes<I> {
+    type Item = std::io::Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remain == 0 {
+            return None;
+        }
+
+        let b = match self.stream.next() {
+            Some(Ok(b)) => b,
+            Some(Err(e)) => return Some(Err(e)),
+            None => return Some(Err(std::io::ErrorKind::UnexpectedEof.into())),
+        };
+
+        self.remain -= 1;
+        Some(Ok(b))
+    }
+}
+
+/// A decoder over an HTTP/1 message body, covering the three ways its framing can be
+/// delimited [RFC7230§3.3.3]: `Transfer-Encoding: chunked`, a known `Content-Length`,
+/// or (for responses only) the end of the connection.
+///
+/// All three variants expose the same `Iterator<Item = io::Result<u8>>` interface, so
+/// a caller can pick the right mode after parsing a message's headers and then decode
+/// its body uniformly.
+///
+/// ## Example
+///
+/// ```rust
+/// use uhttp_chunked_bytes::BodyDecoder;
+///
+/// let body = b"hello world";
+/// let mut decoder = BodyDecoder::length(body.iter().map(|&b| Ok(b)), body.len() as u64);
+///
+/// let mut bytes = Vec::new();
+/// while let Some(b) = decoder.next() {
+///     bytes.push(b.unwrap());
+/// }
+///
+/// assert_eq!(&bytes[..], &body[..]);
+/// ```
+pub enum BodyDecoder<I: Iterator<Item = std::io::Result<u8>>> {
+    /// A chunked transfer-encoded body.
+    Chunked(ChunkedBytes<I>),
+    /// A body with a known length in bytes.
+    Length(LengthBytes<I>),
+    /// A body delimited by the end of the underlying stream.
+    Eof(I),
+}
+
+impl<I: Iterator<Item = std::io::Result<u8>>> BodyDecoder<I> {
+    /// Create a decoder for a chunked transfer-encoded body.
+    pub fn chunked(stream: I) -> Self {
+        BodyDecoder::Chunked(ChunkedBytes::new(stream))
+    }
+
+    /// Create a decoder for a body with the given `Content-Length`.
+    pub fn length(stream: I, len: u64) -> Self {
+        BodyDecoder::Length(LengthBytes::new(stream, len))
+    }
+
+    /// Create a decoder for a body delimited by the end of the stream.
+    pub fn eof(stream: I) -> Self {
+        BodyDecoder::Eof(stream)
+    }
+}
+
+impl<I: Iterator<Item = std::io::Result<u8>>> Iterator for BodyDecoder<I> {
+    type Item = std::io::Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self {
+            BodyDecoder::Chunked(ref mut c) => c.next(),
+            BodyDecoder::Length(ref mut l) => l.next(),
+            BodyDecoder::Eof(ref mut s) => s.next(),
+        }
     }
 }
 
@@ -295,6 +886,269 @@ mod test {
     }
 
 
+    #[test]
+    fn test_length_bytes() {
+        let stream = b"hello";
+        let mut l = LengthBytes::new(stream.iter().map(|&x| Ok(x)), 5);
+        assert_eq!(l.next().unwrap().unwrap(), b'h');
+        assert_eq!(l.next().unwrap().unwrap(), b'e');
+        assert_eq!(l.next().unwrap().unwrap(), b'l');
+        assert_eq!(l.next().unwrap().unwrap(), b'l');
+        assert_eq!(l.next().unwrap().unwrap(), b'o');
+        assert!(l.next().is_none());
+
+        let stream = b"hi";
+        let mut l = LengthBytes::new(stream.iter().map(|&x| Ok(x)), 5);
+        assert_eq!(l.next().unwrap().unwrap(), b'h');
+        assert_eq!(l.next().unwrap().unwrap(), b'i');
+        assert!(l.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_body_decoder() {
+        let stream = b"4\r\nabcd\r\n0\r\n\r\n";
+        let mut d = BodyDecoder::chunked(stream.iter().map(|&x| Ok(x)));
+        assert_eq!(d.next().unwrap().unwrap(), b'a');
+        assert_eq!(d.next().unwrap().unwrap(), b'b');
+        assert_eq!(d.next().unwrap().unwrap(), b'c');
+        assert_eq!(d.next().unwrap().unwrap(), b'd');
+        assert!(d.next().is_none());
+
+        let stream = b"abcd";
+        let mut d = BodyDecoder::length(stream.iter().map(|&x| Ok(x)), 4);
+        assert_eq!(d.next().unwrap().unwrap(), b'a');
+        assert_eq!(d.next().unwrap().unwrap(), b'b');
+        assert_eq!(d.next().unwrap().unwrap(), b'c');
+        assert_eq!(d.next().unwrap().unwrap(), b'd');
+        assert!(d.next().is_none());
+
+        let stream = b"abcd";
+        let mut d = BodyDecoder::eof(stream.iter().map(|&x| Ok(x)));
+        assert_eq!(d.next().unwrap().unwrap(), b'a');
+        assert_eq!(d.next().unwrap().unwrap(), b'b');
+        assert_eq!(d.next().unwrap().unwrap(), b'c');
+        assert_eq!(d.next().unwrap().unwrap(), b'd');
+        assert!(d.next().is_none());
+    }
+
+    #[test]
+    fn test_extension() {
+        let stream = b"4;foo=bar\r\nabcd\r\n2;baz\r\n42\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x))).capture_extensions(true);
+
+        assert_eq!(c.extension(), b"");
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.extension(), b"foo=bar");
+        assert_eq!(c.next().unwrap().unwrap(), b'b');
+        assert_eq!(c.next().unwrap().unwrap(), b'c');
+        assert_eq!(c.next().unwrap().unwrap(), b'd');
+
+        assert_eq!(c.next().unwrap().unwrap(), b'4');
+        assert_eq!(c.extension(), b"baz");
+        assert_eq!(c.next().unwrap().unwrap(), b'2');
+
+        assert!(c.next().is_none());
+
+        // Not enabled by default.
+        let stream = b"4;foo=bar\r\nabcd\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.extension(), b"");
+    }
+
+    #[test]
+    fn test_trailers() {
+        let stream = b"4\r\nabcd\r\n0\r\nA: B\r\nFoo: bar baz\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.next().unwrap().unwrap(), b'b');
+        assert_eq!(c.next().unwrap().unwrap(), b'c');
+        assert_eq!(c.next().unwrap().unwrap(), b'd');
+        assert!(c.next().is_none());
+
+        assert_eq!(
+            c.trailers().unwrap(),
+            vec![
+                (b"A".to_vec(), b"B".to_vec()),
+                (b"Foo".to_vec(), b"bar baz".to_vec()),
+            ]
+        );
+
+        let stream = b"4\r\nabcd\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.next().unwrap().unwrap(), b'b');
+        assert_eq!(c.next().unwrap().unwrap(), b'c');
+        assert_eq!(c.next().unwrap().unwrap(), b'd');
+        assert!(c.next().is_none());
+        assert_eq!(c.trailers().unwrap(), vec![]);
+
+        // Calling before iteration has finished is an error.
+        let stream = b"4\r\nabcd\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert!(c.trailers().is_err());
+    }
+
+    #[test]
+    fn test_resume_after_would_block() {
+        // Stream that yields `WouldBlock` in place of a byte at position `block_at`,
+        // then resumes returning bytes from `data` on the next call at that position.
+        struct Blocking<'a> {
+            data: &'a [u8],
+            pos: usize,
+            block_at: usize,
+            blocked: bool,
+        }
+
+        impl<'a> Iterator for Blocking<'a> {
+            type Item = std::io::Result<u8>;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                if self.pos >= self.data.len() {
+                    return None;
+                }
+
+                if self.pos == self.block_at && !self.blocked {
+                    self.blocked = true;
+                    return Some(Err(std::io::ErrorKind::WouldBlock.into()));
+                }
+
+                let b = self.data[self.pos];
+                self.pos += 1;
+                Some(Ok(b))
+            }
+        }
+
+        // Block in the middle of the chunk-size line, after one digit has already
+        // been accumulated.
+        let stream = Blocking { data: b"10\r\nabcdefghijklmnop\r\n0\r\n\r\n", pos: 0, block_at: 1, blocked: false };
+        let mut c = ChunkedBytes::new(stream);
+        assert_eq!(c.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.next().unwrap().unwrap(), b'b');
+
+        // Block on the chunk's trailing LF, after the last payload byte has already
+        // been consumed from the source but not yet yielded to the caller.
+        let stream = Blocking { data: b"2\r\nab\r\n0\r\n\r\n", pos: 0, block_at: 6, blocked: false };
+        let mut c = ChunkedBytes::new(stream);
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+        assert_eq!(c.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+        assert_eq!(c.next().unwrap().unwrap(), b'b');
+        assert!(c.next().is_none());
+    }
+
+    #[test]
+    fn test_strict() {
+        // Lenient by default: an embedded space defers to `from_str_radix`, which
+        // still rejects it.
+        let stream = b"0 \r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert!(c.next().unwrap().is_err());
+
+        // Strict mode rejects the space immediately instead of deferring.
+        let stream = b"0 \r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x))).strict(true);
+        assert!(c.next().unwrap().is_err());
+
+        // A bare LF in the size line is always rejected.
+        let stream = b"4\nabcd\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x)));
+        assert!(c.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_max_ext_len() {
+        // "foo" is 3 bytes, so a 2-byte bound must reject it.
+        let stream = b"4;foo\r\nabcd\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x))).max_ext_len(Some(2));
+        assert!(c.next().unwrap().is_err());
+
+        let stream = b"4;foo\r\nabcd\r\n0\r\n\r\n";
+        let mut c = ChunkedBytes::new(stream.iter().map(|&x| Ok(x))).max_ext_len(Some(3000));
+        assert_eq!(c.next().unwrap().unwrap(), b'a');
+    }
+
+    #[test]
+    fn test_chunked_slices() {
+        let body = b"4\r\nabcd\r\n3\r\nefg\r\n0\r\n\r\n";
+        let mut slices = ChunkedSlices::new(body);
+        assert_eq!(slices.next().unwrap().unwrap(), b"abcd");
+        assert_eq!(slices.next().unwrap().unwrap(), b"efg");
+        assert!(slices.next().is_none());
+
+        // A chunk extension is skipped over.
+        let body = b"4;foo=bar\r\nabcd\r\n0\r\n\r\n";
+        let mut slices = ChunkedSlices::new(body);
+        assert_eq!(slices.next().unwrap().unwrap(), b"abcd");
+        assert!(slices.next().is_none());
+
+        let body = b"";
+        let mut slices = ChunkedSlices::new(&body[..]);
+        assert!(slices.next().is_none());
+
+        // Missing trailing CRLF after the chunk data.
+        let body = b"4\r\nabcdXX";
+        let mut slices = ChunkedSlices::new(body);
+        assert!(slices.next().unwrap().is_err());
+
+        // Size line never terminated.
+        let body = b"4;foo";
+        let mut slices = ChunkedSlices::new(body);
+        assert!(slices.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_chunked_slices_strict_and_max_ext_len() {
+        // Lenient by default: an embedded space defers to `from_str_radix`.
+        let body = b"0 \r\n";
+        let mut slices = ChunkedSlices::new(&body[..]);
+        assert!(slices.next().unwrap().is_err());
+
+        // Strict mode rejects it immediately instead of deferring.
+        let mut slices = ChunkedSlices::new(&body[..]).strict(true);
+        assert!(slices.next().unwrap().is_err());
+
+        let body = b"4;foo\r\nabcd\r\n0\r\n\r\n";
+        let mut slices = ChunkedSlices::new(&body[..]).max_ext_len(Some(2));
+        assert!(slices.next().unwrap().is_err());
+
+        let mut slices = ChunkedSlices::new(&body[..]).max_ext_len(Some(3));
+        assert_eq!(slices.next().unwrap().unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b"hello"), b"5\r\nhello\r\n0\r\n\r\n");
+        // A zero-length payload isn't framed as its own chunk, so this must be the
+        // bare terminator rather than two terminators concatenated.
+        assert_eq!(encode(b""), b"0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_chunked_encoder() {
+        let mut enc = ChunkedEncoder::new();
+        enc.push(b"hello ");
+        enc.push(b"world");
+        assert_eq!(enc.finish(&[]), b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n");
+
+        let mut enc = ChunkedEncoder::new();
+        enc.push(b"abcdefghij");
+        assert_eq!(
+            enc.finish(&["A: B", "C: D"]),
+            b"a\r\nabcdefghij\r\n0\r\nA: B\r\nC: D\r\n\r\n"
+        );
+
+        assert_eq!(ChunkedEncoder::new().finish(&[]), b"0\r\n\r\n");
+
+        // Pushing a zero-length chunk mid-stream must not inject a spurious
+        // terminator that truncates the chunks pushed after it.
+        let mut enc = ChunkedEncoder::new();
+        enc.push(b"hello");
+        enc.push(b"");
+        enc.push(b"world");
+        assert_eq!(enc.finish(&[]), b"5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n");
+    }
+
     #[cfg(target_pointer_width = "64")]
     #[test]
     fn test_max_size() {